@@ -0,0 +1,176 @@
+//! Load-aware automatic rebalancing, driven from a `metrics::MetricsSnapshot`.
+
+use std::time::{Duration, Instant};
+
+use {Bin, Control, ControlInst};
+use metrics::MetricsSnapshot;
+
+/// Per-worker load, computed by summing the occupancy of the bins each worker owns.
+fn worker_loads(map: &[usize], loads: &[u64], peers: usize) -> Vec<u64> {
+    let mut totals = vec![0u64; peers];
+    for (&worker, &load) in map.iter().zip(loads.iter()) {
+        totals[worker] += load;
+    }
+    totals
+}
+
+fn spread(totals: &[u64]) -> u64 {
+    let max = totals.iter().cloned().max().unwrap_or(0);
+    let min = totals.iter().cloned().min().unwrap_or(0);
+    max - min
+}
+
+/// Per-bin records routed since `previous` was taken. `metrics::Metrics` only ever grows its
+/// bin counters, so the raw snapshot is an all-time total, not a current-load gauge; rebalancing
+/// needs the delta between two snapshots to see what's happening *now*.
+fn bin_deltas(current: &[u64], previous: &[u64]) -> Vec<u64> {
+    current.iter().zip(previous.iter()).map(|(&c, &p)| c.saturating_sub(p)).collect()
+}
+
+/// A pluggable strategy for turning a load snapshot into bin reassignments.
+///
+/// Implementations see only the current bin-to-worker `map` and the per-bin `loads` (indexed
+/// the same way as `map`), so alternative balancing strategies can be swapped into `Controller`
+/// without it needing to know how they work.
+pub trait RebalancePolicy {
+    /// Propose `(bin, new_worker)` reassignments. An empty vector means "leave it as is".
+    fn propose(&self, map: &[usize], loads: &[u64], peers: usize) -> Vec<(Bin, usize)>;
+}
+
+/// Greedily move the heaviest bins off the most-loaded worker and onto the least-loaded one,
+/// one bin at a time, until the max/min load spread is at or under `threshold`, the most-loaded
+/// worker has no bins left, or `max_moves_per_round` moves have been proposed. The heaviest bin
+/// is moved even when its load exceeds the current spread; lighter bins are not tried instead.
+pub struct GreedyRebalance {
+    /// Stop proposing moves once `max_load - min_load <= threshold`.
+    pub threshold: u64,
+    /// Upper bound on how many bins this policy proposes moving in one round.
+    pub max_moves_per_round: usize,
+}
+
+impl GreedyRebalance {
+    /// Construct a policy targeting `threshold` load spread, moving at most
+    /// `max_moves_per_round` bins per round.
+    pub fn new(threshold: u64, max_moves_per_round: usize) -> Self {
+        GreedyRebalance { threshold, max_moves_per_round }
+    }
+}
+
+impl RebalancePolicy for GreedyRebalance {
+    fn propose(&self, map: &[usize], loads: &[u64], peers: usize) -> Vec<(Bin, usize)> {
+        let mut totals = worker_loads(map, loads, peers);
+
+        // Bins owned by each worker, heaviest first, so the heaviest one can be popped off
+        // whichever worker is currently most loaded.
+        let mut bins_by_worker: Vec<Vec<Bin>> = vec![Vec::new(); peers];
+        for (bin, &worker) in map.iter().enumerate() {
+            bins_by_worker[worker].push(Bin(bin));
+        }
+        for bins in &mut bins_by_worker {
+            bins.sort_by_key(|&Bin(bin)| loads[bin]);
+        }
+
+        let mut moves = Vec::new();
+        while moves.len() < self.max_moves_per_round {
+            let heaviest = (0 .. peers).max_by_key(|&w| totals[w]).unwrap();
+            let lightest = (0 .. peers).min_by_key(|&w| totals[w]).unwrap();
+            if heaviest == lightest || spread(&totals) <= self.threshold {
+                break;
+            }
+            let bin = match bins_by_worker[heaviest].pop() {
+                Some(bin) => bin,
+                None => break,
+            };
+            let bin_load = loads[*bin];
+            totals[heaviest] -= bin_load;
+            totals[lightest] += bin_load;
+            // The bin now belongs to `lightest`: keep `bins_by_worker` in sync with `totals` so
+            // it can be picked up again this round if `lightest` later becomes the heaviest.
+            bins_by_worker[lightest].push(bin.clone());
+            bins_by_worker[lightest].sort_by_key(|&Bin(b)| loads[b]);
+            moves.push((bin, lightest));
+        }
+        moves
+    }
+}
+
+/// Drives periodic, load-aware rebalancing on worker 0.
+///
+/// `maybe_rebalance` is meant to be polled once per worker step (or on whatever cadence the
+/// caller already steps the worker); it is a no-op unless `interval` has elapsed since the
+/// last check, `cooldown` has elapsed since the last migration it triggered, and the chosen
+/// `RebalancePolicy` proposes a change that reduces the load spread by at least `min_gain`.
+pub struct Controller<P: RebalancePolicy> {
+    policy: P,
+    peers: usize,
+    interval: Duration,
+    cooldown: Duration,
+    min_gain: u64,
+    sequence: u64,
+    last_check: Option<Instant>,
+    last_migration: Option<Instant>,
+    last_bin_counts: Option<Vec<u64>>,
+}
+
+impl<P: RebalancePolicy> Controller<P> {
+    /// Construct a controller for `peers` workers, checking the metrics snapshot every
+    /// `interval`, waiting `cooldown` after any migration before considering another, and only
+    /// acting on proposals that reduce the load spread by at least `min_gain`.
+    pub fn new(policy: P, peers: usize, interval: Duration, cooldown: Duration, min_gain: u64) -> Self {
+        Controller {
+            policy,
+            peers,
+            interval,
+            cooldown,
+            min_gain,
+            sequence: 0,
+            last_check: None,
+            last_migration: None,
+            last_bin_counts: None,
+        }
+    }
+
+    /// Consider rebalancing given the current `snapshot` and bin-to-worker `map`. Returns a
+    /// `Control` to broadcast if, and only if, it is time to check again, a prior snapshot is
+    /// on hand to diff against, the controller is past its cooldown, and the policy's proposal
+    /// clears `min_gain`.
+    pub fn maybe_rebalance(&mut self, now: Instant, snapshot: &MetricsSnapshot, map: &[usize]) -> Option<Control> {
+        if let Some(last) = self.last_check {
+            if now.duration_since(last) < self.interval {
+                return None;
+            }
+        }
+        self.last_check = Some(now);
+
+        // `snapshot.bin_counts` is all-time; diff against the last snapshot to get this
+        // interval's actual load. The very first check has nothing to diff against yet, so it
+        // just records a baseline.
+        let previous = self.last_bin_counts.replace(snapshot.bin_counts.clone())?;
+        let loads = bin_deltas(&snapshot.bin_counts, &previous);
+
+        if let Some(last) = self.last_migration {
+            if now.duration_since(last) < self.cooldown {
+                return None;
+            }
+        }
+
+        let moves = self.policy.propose(map, &loads, self.peers);
+        if moves.is_empty() {
+            return None;
+        }
+
+        let before = spread(&worker_loads(map, &loads, self.peers));
+        let mut proposed_map = map.to_vec();
+        for &(Bin(bin), worker) in &moves {
+            proposed_map[bin] = worker;
+        }
+        let after = spread(&worker_loads(&proposed_map, &loads, self.peers));
+        if before <= after || before - after < self.min_gain {
+            return None;
+        }
+
+        self.last_migration = Some(now);
+        self.sequence += 1;
+        Some(Control::new(self.sequence, 1, ControlInst::MapDelta(moves)))
+    }
+}
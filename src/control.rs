@@ -0,0 +1,116 @@
+//! Acknowledged, confirmed control instructions.
+//!
+//! `Control` messages are normally broadcast fire-and-forget; `send_and_confirm` instead blocks
+//! until every worker's `ControlAck` for a sequence has been observed by an `AckTracker`,
+//! re-broadcasting on timeout.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use Control;
+
+/// How strongly a `send_and_confirm` caller wants to wait before considering a reconfiguration
+/// done, analogous to commitment levels in a consensus system.
+#[derive(Abomonation, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Confirmation {
+    /// The dataflow frontier has reached the new `ControlSet`'s frontier on every worker, i.e.
+    /// every worker has installed the new map.
+    Applied,
+    /// `Applied`, and the subsequent input frontier has also advanced on every worker, meaning
+    /// any data that was in flight under the old map has fully drained through the new one.
+    Settled,
+}
+
+/// An acknowledgement that `worker` has observed `sequence` reach `level`.
+#[derive(Abomonation, Clone, Debug)]
+pub struct ControlAck {
+    sequence: u64,
+    worker: usize,
+    level: Confirmation,
+}
+
+impl ControlAck {
+    /// Construct a new `ControlAck`.
+    pub fn new(sequence: u64, worker: usize, level: Confirmation) -> Self {
+        ControlAck { sequence, worker, level }
+    }
+
+    /// The sequence number this ack confirms.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The worker that emitted this ack.
+    pub fn worker(&self) -> usize {
+        self.worker
+    }
+
+    /// The confirmation level this ack reached.
+    pub fn level(&self) -> Confirmation {
+        self.level
+    }
+}
+
+/// Tracks acks for in-flight `Control` sequences, as observed by a coordinator.
+#[derive(Default)]
+pub struct AckTracker {
+    acked: HashSet<(u64, usize, Confirmation)>,
+}
+
+impl AckTracker {
+    /// Construct an empty tracker.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record that an ack was observed.
+    pub fn record(&mut self, ack: ControlAck) {
+        self.acked.insert((ack.sequence(), ack.worker(), ack.level()));
+    }
+
+    /// Whether every one of `peers` workers has acked `sequence` at, or past, `level`.
+    pub fn is_confirmed(&self, sequence: u64, peers: usize, level: Confirmation) -> bool {
+        let levels: &[Confirmation] = match level {
+            Confirmation::Applied => &[Confirmation::Applied, Confirmation::Settled],
+            Confirmation::Settled => &[Confirmation::Settled],
+        };
+        (0 .. peers).all(|worker| {
+            levels.iter().any(|&level| self.acked.contains(&(sequence, worker, level)))
+        })
+    }
+}
+
+/// Submit `control` and block until `tracker` shows every one of `peers` workers has
+/// acknowledged its sequence at, or past, `level`, re-broadcasting `control` through `send`
+/// whenever `timeout` elapses without full confirmation.
+///
+/// `send` (re-)submits `control` on the control input; `step` advances the worker (and its
+/// control input/feedback stream) by one unit of work and returns whatever `ControlAck`s that
+/// produced, which are recorded into `tracker`. Returns once confirmed.
+pub fn send_and_confirm<Send, Step>(
+    control: Control,
+    peers: usize,
+    level: Confirmation,
+    timeout: Duration,
+    tracker: &mut AckTracker,
+    mut send: Send,
+    mut step: Step,
+)
+where
+    Send: FnMut(Control),
+    Step: FnMut() -> Vec<ControlAck>,
+{
+    let sequence = control.sequence();
+    send(control.clone());
+
+    let mut deadline = Instant::now() + timeout;
+    while !tracker.is_confirmed(sequence, peers, level) {
+        for ack in step() {
+            tracker.record(ack);
+        }
+        if Instant::now() >= deadline {
+            send(control.clone());
+            deadline = Instant::now() + timeout;
+        }
+    }
+}
@@ -8,10 +8,17 @@ pub mod distribution;
 pub mod stateful;
 pub mod state_machine;
 pub mod join;
+pub mod metrics;
+pub mod control;
+pub mod pool;
+pub mod autoscale;
 
 use timely::order::PartialOrder;
 use timely::progress::frontier::Antichain;
 
+use metrics::Metrics;
+use control::{ControlAck, Confirmation};
+
 /// A control message consisting of a sequence number, a total count of messages to be expected
 /// and an instruction.
 #[derive(Abomonation, Clone, Debug)]
@@ -44,6 +51,15 @@ pub enum ControlInst {
     Map(Vec<usize>),
     /// Provide a map update
     Move(Bin, /*worker*/ usize),
+    /// Provide a sparse set of bin-to-worker point updates, applied over the previous map.
+    ///
+    /// Unlike `Map`, which ships the full `1 << BIN_SHIFT` vector on every reconfiguration,
+    /// this only carries the bins that actually move, which matters once `BIN_SHIFT` is large
+    /// enough that most reconfigurations touch only a handful of bins.
+    MapDelta(Vec<(Bin, /*worker*/ usize)>),
+    /// Reassign the contiguous bin range `[start, end)` to `worker`, applied over the previous
+    /// map without materializing a full replacement vector.
+    MoveRange(/*start*/ Bin, /*end*/ Bin, /*worker*/ usize),
     /// No-op
     None,
 }
@@ -53,6 +69,11 @@ impl Control {
     pub fn new(sequence: u64, count: usize, inst: ControlInst) -> Self {
         Self { sequence, count, inst }
     }
+
+    /// The sequence number identifying the reconfiguration this `Control` is part of.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
 }
 
 /// A compiled set of control instructions
@@ -73,6 +94,26 @@ impl<T> ControlSet<T> {
         &self.map
     }
 
+    /// Build the `ControlAck` that `worker` should emit once it has locally observed this
+    /// `ControlSet` reach `level` (e.g. the dataflow frontier having advanced past
+    /// `self.frontier` for `Confirmation::Applied`).
+    ///
+    /// Constructing the ack here keeps the sequence number in one place; the operator that
+    /// watches the frontier is responsible for actually sending it on the feedback stream.
+    ///
+    /// BLOCKER: nothing in this tree calls this. Emitting it requires an operator that owns
+    /// the frontier a `ControlSet` was built against — i.e. whatever consumes
+    /// `ControlSetBuilder::build` and applies its `map` (the control-plan operator, presumably
+    /// in `distribution.rs` or `bin_prober.rs`) — watching that frontier advance and pushing
+    /// the resulting ack onto a feedback stream broadcast to every worker. Neither module
+    /// exists in this tree, and `state_machine.rs`'s frontier is over its own key/value input,
+    /// not a `ControlSet`'s, so it isn't the right place to add this either. `control::{
+    /// AckTracker, send_and_confirm}` are ready to consume acks once something produces them,
+    /// but the production side stays unimplemented here.
+    pub fn ack(&self, worker: usize, level: Confirmation) -> ControlAck {
+        ControlAck::new(self.sequence, worker, level)
+    }
+
 }
 
 #[derive(Default)]
@@ -110,7 +151,9 @@ impl<T: PartialOrder> ControlSetBuilder<T> {
         self.frontier.extend(caps);
     }
 
-    pub fn build(self, previous: &ControlSet<T>) -> ControlSet<T> {
+    pub fn build(self, previous: &ControlSet<T>, metrics: &Metrics) -> ControlSet<T> {
+        let timer = ::std::time::Instant::now();
+
         assert_eq!(0, self.count.unwrap_or(0));
         let mut frontier = Antichain::new();
         for f in self.frontier {frontier.insert(f);}
@@ -124,15 +167,29 @@ impl<T: PartialOrder> ControlSetBuilder<T> {
                     map.extend( new_map.iter());
                 },
                 ControlInst::Move(Bin(bin), target) => map[bin] = target,
+                ControlInst::MapDelta(ref updates) => {
+                    for &(Bin(bin), target) in updates {
+                        map[bin] = target;
+                    }
+                },
+                ControlInst::MoveRange(Bin(start), Bin(end), target) => {
+                    for slot in &mut map[start .. end] {
+                        *slot = target;
+                    }
+                },
                 ControlInst::None => {},
             }
         }
 
-        ControlSet {
+        let control_set = ControlSet {
             sequence: self.sequence.unwrap(),
             frontier,
             map,
-        }
+        };
+
+        metrics.record_migration(timer.elapsed());
+
+        control_set
     }
 }
 
@@ -0,0 +1,161 @@
+//! Lock-free metrics for bin occupancy and reconfiguration cost.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use BIN_SHIFT;
+
+/// Number of sub-buckets per power-of-two magnitude in a `LatencyHistogram`.
+///
+/// Plain `leading_zeros` bucketing doubles its bucket width with every step, which is too
+/// coarse for migration latencies that might range from microseconds to seconds. Splitting
+/// each magnitude into this many linear sub-buckets gives finer resolution without giving up
+/// the O(1), allocation-free nature of the scheme.
+const DEFAULT_SUB_BUCKETS: usize = 4;
+
+/// An HDR-style logarithmic latency histogram backed by `AtomicU64` counters.
+///
+/// Values are bucketed by magnitude (`64 - v.leading_zeros()`), then linearly refined within
+/// that magnitude into `sub_buckets` slots. Recording a value is a single
+/// `fetch_add(1, Relaxed)`, so it is safe to call from any number of concurrent writers.
+pub struct LatencyHistogram {
+    sub_buckets: usize,
+    buckets: Vec<AtomicU64>,
+}
+
+impl LatencyHistogram {
+    /// Construct a histogram refining each power-of-two magnitude into `sub_buckets` slots.
+    pub fn new(sub_buckets: usize) -> Self {
+        let sub_buckets = sub_buckets.max(1);
+        let buckets = (0 .. 64 * sub_buckets).map(|_| AtomicU64::new(0)).collect();
+        LatencyHistogram { sub_buckets, buckets }
+    }
+
+    fn bucket_of(&self, value: u64) -> usize {
+        let magnitude = 64 - value.leading_zeros() as usize;
+        if magnitude == 0 {
+            return 0;
+        }
+        let base = 1u64 << (magnitude - 1);
+        let offset = value - base;
+        // `offset * sub_buckets` can overflow a `u64` for `value` near `1 << 63`, so widen to
+        // `u128` for the multiply rather than narrowing the range it has to work in.
+        let sub_bucket = (offset as u128 * self.sub_buckets as u128 / base as u128) as usize;
+        ((magnitude - 1) * self.sub_buckets + sub_bucket).min(self.buckets.len() - 1)
+    }
+
+    /// Record a single observation, in whatever unit the caller is consistent about.
+    pub fn record(&self, value: u64) {
+        self.buckets[self.bucket_of(value)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read all bucket counts without resetting them.
+    pub fn counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect()
+    }
+}
+
+/// Lock-free counters instrumenting the dynamic scaling mechanism for a single worker.
+pub struct Metrics {
+    /// Records routed to each bin, indexed by bin id. Sized `1 << BIN_SHIFT`.
+    bin_counts: Vec<AtomicU64>,
+    /// Number of state entries currently live, i.e. created but not yet deregistered.
+    live_states: AtomicU64,
+    /// Total nanoseconds spent applying `ControlSet`s, across all reconfigurations.
+    migration_nanos_total: AtomicU64,
+    /// Distribution of per-reconfiguration wall-clock cost, in nanoseconds.
+    migration_latency: LatencyHistogram,
+}
+
+impl Metrics {
+    /// Construct a fresh set of counters, one bin slot per `1 << BIN_SHIFT`.
+    pub fn new() -> Self {
+        Metrics {
+            bin_counts: (0 .. 1 << BIN_SHIFT).map(|_| AtomicU64::new(0)).collect(),
+            live_states: AtomicU64::new(0),
+            migration_nanos_total: AtomicU64::new(0),
+            migration_latency: LatencyHistogram::new(DEFAULT_SUB_BUCKETS),
+        }
+    }
+
+    /// Record that a record was routed to `bin`.
+    pub fn record_bin(&self, bin: usize) {
+        self.bin_counts[bin].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a new state entry was created.
+    pub fn state_created(&self) {
+        self.live_states.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a state entry was deregistered.
+    pub fn state_removed(&self) {
+        self.live_states.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Record the wall-clock cost of applying a single `ControlSet`.
+    pub fn record_migration(&self, elapsed: Duration) {
+        let nanos = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+        self.migration_nanos_total.fetch_add(nanos, Ordering::Relaxed);
+        self.migration_latency.record(nanos);
+    }
+
+    /// Take a point-in-time snapshot of all counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            bin_counts: self.bin_counts.iter().map(|count| count.load(Ordering::Relaxed)).collect(),
+            live_states: self.live_states.load(Ordering::Relaxed),
+            migration_nanos_total: self.migration_nanos_total.load(Ordering::Relaxed),
+            migration_latency_counts: self.migration_latency.counts(),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// A plain, point-in-time copy of a `Metrics` instance's counters.
+#[derive(Clone, Debug)]
+pub struct MetricsSnapshot {
+    /// Records routed to each bin, indexed by bin id.
+    pub bin_counts: Vec<u64>,
+    /// Number of state entries currently live at the time of the snapshot.
+    pub live_states: u64,
+    /// Total nanoseconds spent applying `ControlSet`s, across all reconfigurations so far.
+    pub migration_nanos_total: u64,
+    /// Bucket counts backing the migration latency distribution.
+    migration_latency_counts: Vec<u64>,
+}
+
+impl MetricsSnapshot {
+    /// Estimate the `p`-th percentile (0.0 ..= 100.0) of migration latency, in nanoseconds.
+    ///
+    /// The estimate is the lower bound of the bucket holding that percentile, so it
+    /// under-reports by at most the width of one bucket.
+    pub fn migration_latency_percentile(&self, p: f64) -> u64 {
+        let total: u64 = self.migration_latency_counts.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+        let sub_buckets = DEFAULT_SUB_BUCKETS;
+        let target = ((p / 100.0) * total as f64).ceil() as u64;
+        let mut seen = 0u64;
+        for (i, &count) in self.migration_latency_counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                // Bucket 0 holds both 0 and 1, so its lower bound is 0.
+                if i == 0 {
+                    return 0;
+                }
+                let magnitude = i / sub_buckets;
+                let sub_bucket = i % sub_buckets;
+                let base = 1u64 << magnitude;
+                return base + (sub_bucket as u64 * base / sub_buckets as u64);
+            }
+        }
+        0
+    }
+}
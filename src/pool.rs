@@ -0,0 +1,41 @@
+//! A single-threaded free-list for recycling per-key state values.
+
+use std::cell::RefCell;
+
+/// A type that can be returned to its "freshly registered" state in place, without discarding
+/// whatever internal capacity it has already allocated.
+pub trait Reset {
+    /// Clear `self` back to the equivalent of `Default::default()`, keeping capacity.
+    fn reset(&mut self);
+}
+
+/// A free-list of recycled `D` values, local to one worker thread.
+pub struct Pool<D> {
+    free: RefCell<Vec<D>>,
+}
+
+impl<D> Pool<D> {
+    /// Construct an empty pool.
+    pub fn new() -> Self {
+        Pool { free: RefCell::new(Vec::new()) }
+    }
+
+    /// Pop a recycled value, if the pool has one.
+    pub fn pop(&self) -> Option<D> {
+        self.free.borrow_mut().pop()
+    }
+}
+
+impl<D: Reset> Pool<D> {
+    /// Reset `value` in place and push it onto the pool for later reuse.
+    pub fn push(&self, mut value: D) {
+        value.reset();
+        self.free.borrow_mut().push(value);
+    }
+}
+
+impl<D> Default for Pool<D> {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
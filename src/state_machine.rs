@@ -1,5 +1,6 @@
 //! General purpose state transition operator.
 use std::hash::Hash;
+use std::rc::Rc;
 
 use fnv::FnvHashMap as HashMap;
 
@@ -11,8 +12,36 @@ use timely::Data;
 use timely::dataflow::operators::Operator;
 
 use stateful::{StateHandle, StateStream};
+use metrics::Metrics;
+use pool::{Pool, Reset};
+use BIN_SHIFT;
 
-pub trait BinnedStateMachine<S: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData, D: ExchangeData + Default + 'static> {
+/// Acquire a state to back a newly-(re)registered key, preferring a recycled slot from `pool`
+/// over allocating a fresh `Default::default()`.
+#[cfg(not(feature = "disable-state-pool"))]
+fn acquire_state<D: Default>(pool: &Pool<D>) -> D {
+    pool.pop().unwrap_or_default()
+}
+
+/// Disabled via the `disable-state-pool` feature: always allocate, matching the pre-pooling
+/// behavior exactly.
+#[cfg(feature = "disable-state-pool")]
+fn acquire_state<D: Default>(_pool: &Pool<D>) -> D {
+    Default::default()
+}
+
+/// Return a deregistered state to `pool` for reuse. `Pool::push` resets it via `Reset::reset`
+/// before storing it, so the next `acquire_state` gets back a state that is logically fresh
+/// while keeping whatever capacity it had already grown.
+#[cfg(not(feature = "disable-state-pool"))]
+fn release_state<D: Reset>(pool: &Pool<D>, state: D) {
+    pool.push(state);
+}
+
+#[cfg(feature = "disable-state-pool")]
+fn release_state<D>(_pool: &Pool<D>, _state: D) {}
+
+pub trait BinnedStateMachine<S: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData, D: ExchangeData + Default + Reset + 'static> {
     /// Tracks a state for each presented key, using user-supplied state transition logic.
     ///
     /// The transition logic `fold` may mutate the state, and produce both output records and
@@ -26,7 +55,7 @@ pub trait BinnedStateMachine<S: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData,
         R: Data,                                    // output type
         I: IntoIterator<Item=R>,                    // type of output iterator
         F: Fn(&K, V, &mut D)->(bool, I)+'static,    // state update logic
-    >(&mut self, fold: F) -> Stream<S, R> where S::Timestamp : Hash+Eq ;
+    >(&mut self, fold: F, metrics: Rc<Metrics>) -> Stream<S, R> where S::Timestamp : Hash+Eq ;
 }
 
 impl<S, K, V, D> BinnedStateMachine<S, K, V, D> for StateStream<S, (K, V), HashMap<K, D>, (K, D)>
@@ -34,18 +63,28 @@ where
     S: Scope,
     K: ExchangeData+Hash+Eq,
     V: ExchangeData,
-    D: ExchangeData + Default + 'static,
+    D: ExchangeData + Default + Reset + 'static,
 {
     fn state_machine<
         R: Data,                                    // output type
         I: IntoIterator<Item=R>,                    // type of output iterator
         F: Fn(&K, V, &mut D) -> (bool, I) + 'static,    // state update logic
-    >(&mut self, fold: F) -> Stream<S, R> where S::Timestamp: Hash + Eq {
+    >(&mut self, fold: F, metrics: Rc<Metrics>) -> Stream<S, R> where S::Timestamp: Hash + Eq {
         // times -> Vec<input>
         let mut pending: HashMap<_, Vec<(_, _, (K, V))>> = Default::default();
 
         let states = self.state.clone();
 
+        // BLOCKER: the request asks for these pools to live on `stateful::StateHandle`, one per
+        // bin, so that every operator sharing that bin's state also shares its recycled values.
+        // `stateful.rs` does not exist in this tree, so `StateHandle` cannot be extended here.
+        // As a stand-in, pools are constructed fresh per `state_machine` call: recycling only
+        // happens within a single operator invocation's lifetime, not across operators or
+        // across a dataflow being torn down and rebuilt. This is a real scope reduction from
+        // what was requested, not a cosmetic detail — revisit once `stateful::StateHandle`
+        // exists and can own the pools instead.
+        let pools: Vec<Pool<D>> = (0 .. 1 << BIN_SHIFT).map(|_| Pool::new()).collect();
+
         self.stream.unary_frontier(Pipeline, "StateMachine", |_cap, _info| {
             move |input, output| {
 
@@ -57,12 +96,22 @@ where
                     if let Some(pend) = pending.remove(time.time()) {
                         let mut session = output.session(&time);
                         for (_target, key_id, (key, val)) in pend {
+                            metrics.record_bin(key_id);
                             let mut states = states.get_state(key_id);
+                            let pool = &pools[key_id];
                             let (remove, output) = {
-                                let state = states.entry(key.clone()).or_insert_with(Default::default);
+                                let state = states.entry(key.clone()).or_insert_with(|| {
+                                    metrics.state_created();
+                                    acquire_state(pool)
+                                });
                                 fold(&key, val.clone(), state)
                             };
-                            if remove { states.remove(&key); }
+                            if remove {
+                                if let Some(state) = states.remove(&key) {
+                                    release_state(pool, state);
+                                }
+                                metrics.state_removed();
+                            }
                             session.give_iterator(output.into_iter());
                         }
                     }
@@ -78,12 +127,22 @@ where
                         // else we can process immediately
                         let mut session = output.session(&time);
                         for (_target, key_id, (key, val)) in data.drain(..) {
+                            metrics.record_bin(key_id);
                             let mut states = states.get_state(key_id);
+                            let pool = &pools[key_id];
                             let (remove, output) = {
-                                let state = states.entry(key.clone()).or_insert_with(Default::default);
+                                let state = states.entry(key.clone()).or_insert_with(|| {
+                                    metrics.state_created();
+                                    acquire_state(pool)
+                                });
                                 fold(&key, val.clone(), state)
                             };
-                            if remove { states.remove(&key); }
+                            if remove {
+                                if let Some(state) = states.remove(&key) {
+                                    release_state(pool, state);
+                                }
+                                metrics.state_removed();
+                            }
                             session.give_iterator(output.into_iter());
                         }
                     }